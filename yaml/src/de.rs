@@ -9,10 +9,14 @@
 //! YAML Deserialization
 //!
 //! This module provides YAML deserialization with the type `Deserializer`.
+//! `Deserializer::from_str` iterates over the `---`-separated documents in a
+//! YAML stream; `from_str` is a convenience wrapper for the common case of a
+//! single document.
 
 use std::collections::BTreeMap;
 use std::fmt;
 use std::io;
+use std::marker::PhantomData;
 use std::str;
 
 use yaml_rust::parser::{Parser, MarkedEventReceiver, Event as YamlEvent};
@@ -25,8 +29,17 @@ use super::error::{Error, Result};
 
 pub struct Loader {
     events: Vec<(Event, Marker)>,
-    /// Map from alias id to index in events.
-    aliases: BTreeMap<usize, usize>,
+    /// Map from alias id to index in events, one per document since anchors
+    /// defined in one document are not visible from another.
+    aliases: Vec<BTreeMap<usize, usize>>,
+    /// Index into `events` where each document begins.
+    document_starts: Vec<usize>,
+}
+
+impl Loader {
+    fn current_aliases(&mut self) -> &mut BTreeMap<usize, usize> {
+        self.aliases.last_mut().expect("document start was not recorded")
+    }
 }
 
 impl MarkedEventReceiver for Loader {
@@ -35,21 +48,29 @@ impl MarkedEventReceiver for Loader {
             YamlEvent::Nothing
                 | YamlEvent::StreamStart
                 | YamlEvent::StreamEnd
-                | YamlEvent::DocumentStart
                 | YamlEvent::DocumentEnd => return,
 
+            YamlEvent::DocumentStart => {
+                self.document_starts.push(self.events.len());
+                self.aliases.push(BTreeMap::new());
+                return;
+            }
+
             YamlEvent::Alias(id) => Event::Alias(id),
             YamlEvent::Scalar(ref value, style, id, ref tag) => {
-                self.aliases.insert(id, self.events.len());
+                let pos = self.events.len();
+                self.current_aliases().insert(id, pos);
                 Event::Scalar(value.clone(), style, tag.clone())
             }
             YamlEvent::SequenceStart(id) => {
-                self.aliases.insert(id, self.events.len());
+                let pos = self.events.len();
+                self.current_aliases().insert(id, pos);
                 Event::SequenceStart
             }
             YamlEvent::SequenceEnd => Event::SequenceEnd,
             YamlEvent::MappingStart(id) => {
-                self.aliases.insert(id, self.events.len());
+                let pos = self.events.len();
+                self.current_aliases().insert(id, pos);
                 Event::MappingStart
             }
             YamlEvent::MappingEnd => Event::MappingEnd,
@@ -68,14 +89,14 @@ enum Event {
     MappingEnd,
 }
 
-struct Deserializer<'a> {
+struct EventDeserializer<'a> {
     events: &'a [(Event, Marker)],
     /// Map from alias id to index in events.
     aliases: &'a BTreeMap<usize, usize>,
     pos: usize,
 }
 
-impl<'a> Deserializer<'a> {
+impl<'a> EventDeserializer<'a> {
     fn peek(&self) -> Result<(&'a Event, Marker)> {
         match self.events.get(self.pos) {
             Some(event) => Ok((&event.0, event.1)),
@@ -93,10 +114,34 @@ impl<'a> Deserializer<'a> {
         }
     }
 
-    fn jump(&self, id: usize) -> Result<Deserializer<'a>> {
+    /// Returns the application-specific tag (e.g. `"!Point"`) attached to
+    /// the upcoming event, or an empty string if it is untagged or only
+    /// carries a core-schema `!!` tag. Does not consume the event: only
+    /// `Event::Scalar` records a tag at all, so sequences and mappings
+    /// always report an empty tag here. Follows `Event::Alias` so that an
+    /// alias to a tagged anchor reports the anchor's tag rather than an
+    /// empty one.
+    fn peek_tag(&self) -> Result<String> {
+        let mut de = EventDeserializer {
+            events: self.events,
+            aliases: self.aliases,
+            pos: self.pos,
+        };
+        loop {
+            match *de.peek()?.0 {
+                Event::Alias(i) => de = de.jump(i)?,
+                Event::Scalar(_, _, Some(TokenType::Tag(ref handle, ref suffix))) if handle != "!!" => {
+                    return Ok(format!("{}{}", handle, suffix));
+                }
+                _ => return Ok(String::new()),
+            }
+        }
+    }
+
+    fn jump(&self, id: usize) -> Result<EventDeserializer<'a>> {
         match self.aliases.get(&id) {
             Some(&pos) => {
-                Ok(Deserializer {
+                Ok(EventDeserializer {
                     events: self.events,
                     aliases: self.aliases,
                     pos: pos,
@@ -112,6 +157,22 @@ impl<'a> Deserializer<'a> {
         match *self.next()?.0 {
             Event::Alias(i) => de::Deserializer::deserialize(&mut self.jump(i)?, visitor),
             Event::Scalar(ref v, style, ref tag) => {
+                // `!!binary` is checked ahead of the plain-style gate below:
+                // real payloads are base64 blobs almost always written as a
+                // literal block scalar or quoted, never bare-plain, so the
+                // style check alone would leave this branch unreachable.
+                if let Some(TokenType::Tag(ref handle, ref suffix)) = *tag {
+                    if handle == "!!" && suffix == "binary" {
+                        let stripped: String =
+                            v.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+                        return match decode_base64(&stripped) {
+                            Some(bytes) => visitor.visit_byte_buf(bytes),
+                            None => Err(de::Error::invalid_value(
+                                Unexpected::Str(v), &"base64-encoded binary data",
+                            )),
+                        };
+                    }
+                }
                 if style != TScalarStyle::Plain {
                     visitor.visit_str(v)
                 } else if let Some(TokenType::Tag(ref handle, ref suffix)) = *tag {
@@ -124,9 +185,9 @@ impl<'a> Deserializer<'a> {
                                 }
                             },
                             "int" => {
-                                match v.parse::<i64>() {
-                                    Ok(v) => visitor.visit_i64(v),
-                                    Err(_) => Err(de::Error::invalid_value(Unexpected::Str(v), &"an integer")),
+                                match parse_int(v, 10) {
+                                    Some(n) => n.visit(visitor),
+                                    None => Err(de::Error::invalid_value(Unexpected::Str(v), &"an integer")),
                                 }
                             },
                             "float" => {
@@ -144,7 +205,11 @@ impl<'a> Deserializer<'a> {
                             _  => visitor.visit_str(v),
                         }
                     } else {
-                        visitor.visit_str(v)
+                        // An application-specific tag such as `!Point`. The
+                        // tag itself is only reachable through `TaggedValue`;
+                        // a direct deserialize of the payload interprets it
+                        // the same way an untagged scalar would.
+                        visit_untagged_str(visitor, v)
                     }
                 } else {
                     visit_untagged_str(visitor, v)
@@ -223,7 +288,7 @@ impl<'a> Deserializer<'a> {
 }
 
 struct CollectionVisitor<'a: 'r, 'r> {
-    de: &'r mut Deserializer<'a>,
+    de: &'r mut EventDeserializer<'a>,
     len: usize,
 }
 
@@ -266,7 +331,7 @@ impl<'a, 'r> de::MapVisitor for CollectionVisitor<'a, 'r> {
 }
 
 struct VariantVisitor<'a: 'r, 'r> {
-    de: &'r mut Deserializer<'a>,
+    de: &'r mut EventDeserializer<'a>,
 }
 
 impl<'a, 'r> de::EnumVisitor for VariantVisitor<'a, 'r> {
@@ -314,7 +379,7 @@ impl<'a, 'r> de::VariantVisitor for VariantVisitor<'a, 'r> {
 }
 
 struct UnitVariantVisitor<'a: 'r, 'r> {
-    de: &'r mut Deserializer<'a>,
+    de: &'r mut EventDeserializer<'a>,
 }
 
 impl<'a, 'r> de::EnumVisitor for UnitVariantVisitor<'a, 'r> {
@@ -361,6 +426,155 @@ impl<'a, 'r> de::VariantVisitor for UnitVariantVisitor<'a, 'r> {
     }
 }
 
+/// Feeds a fixed field name into a `DeserializeSeed`, used to synthesize the
+/// `tag`/`value` keys of a `TaggedValue` since those names come from Rust
+/// source, not from a YAML event.
+struct FieldNameDeserializer<'f> {
+    name: &'f str,
+}
+
+impl<'f> de::Deserializer for FieldNameDeserializer<'f> {
+    type Error = Error;
+
+    fn deserialize<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        visitor.visit_str(self.name)
+    }
+
+    forward_to_deserialize!{
+        bool u8 u16 u32 u64 i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        unit option seq seq_fixed_size bytes byte_buf map unit_struct
+        tuple_struct struct struct_field tuple ignored_any
+    }
+}
+
+/// A synthetic two-entry map visited by `TaggedValue`'s deserializer,
+/// presenting `{tag: <attached tag>, value: <payload, tag stripped>}` in
+/// place of the single tagged event.
+struct TaggedValueVisitor<'a: 'r, 'r> {
+    tag: String,
+    de: &'r mut EventDeserializer<'a>,
+    index: usize,
+}
+
+impl<'a, 'r> de::MapVisitor for TaggedValueVisitor<'a, 'r> {
+    type Error = Error;
+
+    fn visit_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+        where K: DeserializeSeed
+    {
+        match self.index {
+            0 => seed.deserialize(FieldNameDeserializer { name: "tag" }).map(Some),
+            1 => seed.deserialize(FieldNameDeserializer { name: "value" }).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn visit_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+        where V: DeserializeSeed
+    {
+        let index = self.index;
+        self.index += 1;
+        match index {
+            0 => seed.deserialize(FieldNameDeserializer { name: &self.tag }),
+            1 => seed.deserialize(&mut *self.de),
+            _ => unreachable!("TaggedValue only has a tag and a value field"),
+        }
+    }
+}
+
+/// An integer scalar widened to the smallest of `i64`, `u64`, `i128`, `u128`
+/// that can hold it, so that 64-bit IDs and hashes above `i64::MAX` round
+/// trip instead of overflowing or losing precision to `f64`.
+enum Int {
+    I64(i64),
+    U64(u64),
+    I128(i128),
+    U128(u128),
+}
+
+impl Int {
+    fn visit<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        match self {
+            Int::I64(n) => visitor.visit_i64(n),
+            Int::U64(n) => visitor.visit_u64(n),
+            Int::I128(n) => visitor.visit_i128(n),
+            Int::U128(n) => visitor.visit_u128(n),
+        }
+    }
+}
+
+/// Parses `v` in the given `radix`, trying `i64`, `u64`, `i128`, and `u128`
+/// in turn and returning the first one that fits.
+fn parse_int(v: &str, radix: u32) -> Option<Int> {
+    if let Ok(n) = i64::from_str_radix(v, radix) {
+        return Some(Int::I64(n));
+    }
+    if let Ok(n) = u64::from_str_radix(v, radix) {
+        return Some(Int::U64(n));
+    }
+    if let Ok(n) = i128::from_str_radix(v, radix) {
+        return Some(Int::I128(n));
+    }
+    if let Ok(n) = u128::from_str_radix(v, radix) {
+        return Some(Int::U128(n));
+    }
+    None
+}
+
+/// Decodes a standard-alphabet base64 string (`A`-`Z`, `a`-`z`, `0`-`9`, `+`,
+/// `/`, padded with `=`) as produced by YAML's `!!binary` tag. Returns `None`
+/// if `s` is not a validly padded, validly encoded base64 string.
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    fn sextet(byte: u8) -> Option<u8> {
+        match byte {
+            b'A'..=b'Z' => Some(byte - b'A'),
+            b'a'..=b'z' => Some(byte - b'a' + 26),
+            b'0'..=b'9' => Some(byte - b'0' + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let bytes = s.as_bytes();
+    if bytes.len() % 4 != 0 {
+        return None;
+    }
+
+    let groups = bytes.len() / 4;
+    let mut decoded = Vec::with_capacity(groups * 3);
+    for (i, group) in bytes.chunks(4).enumerate() {
+        let pad = group.iter().rev().take_while(|&&b| b == b'=').count();
+        if pad > 2 || (pad > 0 && i + 1 != groups)
+            || group[..4 - pad].iter().any(|&b| b == b'=')
+        {
+            return None;
+        }
+
+        let mut sextets = [0u8; 4];
+        for (sextet_out, &byte) in sextets.iter_mut().zip(group) {
+            *sextet_out = if byte == b'=' { 0 } else { sextet(byte)? };
+        }
+        let n = (sextets[0] as u32) << 18
+            | (sextets[1] as u32) << 12
+            | (sextets[2] as u32) << 6
+            | sextets[3] as u32;
+
+        decoded.push((n >> 16) as u8);
+        if pad < 2 {
+            decoded.push((n >> 8) as u8);
+        }
+        if pad < 1 {
+            decoded.push(n as u8);
+        }
+    }
+    Some(decoded)
+}
+
 fn visit_untagged_str<V>(visitor: V, v: &str) -> Result<V::Value>
     where V: de::Visitor
 {
@@ -374,22 +588,22 @@ fn visit_untagged_str<V>(visitor: V, v: &str) -> Result<V::Value>
         return visitor.visit_bool(false);
     }
     if v.starts_with("0x") {
-        if let Ok(n) = i64::from_str_radix(&v[2..], 16) {
-            return visitor.visit_i64(n);
+        if let Some(n) = parse_int(&v[2..], 16) {
+            return n.visit(visitor);
         }
     }
     if v.starts_with("0o") {
-        if let Ok(n) = i64::from_str_radix(&v[2..], 8) {
-            return visitor.visit_i64(n);
+        if let Some(n) = parse_int(&v[2..], 8) {
+            return n.visit(visitor);
         }
     }
     if v.starts_with('+') {
-        if let Ok(n) = v[1..].parse() {
-            return visitor.visit_i64(n);
+        if let Some(n) = parse_int(&v[1..], 10) {
+            return n.visit(visitor);
         }
     }
-    if let Ok(n) = v.parse() {
-        return visitor.visit_i64(n);
+    if let Some(n) = parse_int(v, 10) {
+        return n.visit(visitor);
     }
     if let Ok(n) = v.parse() {
         return visitor.visit_f64(n);
@@ -397,7 +611,7 @@ fn visit_untagged_str<V>(visitor: V, v: &str) -> Result<V::Value>
     visitor.visit_str(v)
 }
 
-impl<'a, 'r> de::Deserializer for &'r mut Deserializer<'a> {
+impl<'a, 'r> de::Deserializer for &'r mut EventDeserializer<'a> {
     type Error = Error;
 
     fn deserialize<V>(self, visitor: V) -> Result<V::Value>
@@ -489,38 +703,131 @@ impl<'a, 'r> de::Deserializer for &'r mut Deserializer<'a> {
         }
     }
 
+    /// Parses a struct, with one exception: the sentinel struct name used by
+    /// `TaggedValue` is recognized and handled by synthesizing a map of its
+    /// `tag` and `value` fields from the current event's tag and payload,
+    /// rather than being parsed as an ordinary YAML mapping.
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        if name == super::value::TAGGED_VALUE_NAME {
+            let tag = self.peek_tag()?;
+            visitor.visit_map(TaggedValueVisitor { tag: tag, de: self, index: 0 })
+        } else {
+            self.deserialize(visitor)
+        }
+    }
+
     forward_to_deserialize!{
-        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit seq
-        seq_fixed_size bytes byte_buf map unit_struct tuple_struct struct
+        bool u8 u16 u32 u64 i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        unit seq seq_fixed_size bytes byte_buf map unit_struct tuple_struct
         struct_field tuple ignored_any
     }
 }
 
+/// A deserializer for a stream of one or more `---`-separated YAML
+/// documents.
+///
+/// Iterating yields one deserialized value per document. This is useful for
+/// formats like Kubernetes manifests or Jupyter notebooks that pack several
+/// independent documents into a single YAML stream.
+///
+/// ```no_run
+/// # use serde_yaml::Deserializer;
+/// # fn example() -> serde_yaml::Result<()> {
+/// for document in Deserializer::<i64>::from_str("1\n---\n2\n")? {
+///     println!("{:?}", document?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Deserializer<T> {
+    events: Vec<(Event, Marker)>,
+    /// Map from alias id to index in events, one per document.
+    aliases: Vec<BTreeMap<usize, usize>>,
+    /// Index into `events` where each document begins.
+    document_starts: Vec<usize>,
+    document_index: usize,
+    output: PhantomData<T>,
+}
+
+impl<T> Deserializer<T>
+    where T: Deserialize
+{
+    /// Parses every `---`-separated document in `s`, returning an iterator
+    /// that deserializes one value per document.
+    pub fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser::new(s.chars());
+        let mut loader = Loader {
+            events: Vec::new(),
+            aliases: Vec::new(),
+            document_starts: Vec::new(),
+        };
+        parser.load(&mut loader, true)?;
+        Ok(Deserializer {
+            events: loader.events,
+            aliases: loader.aliases,
+            document_starts: loader.document_starts,
+            document_index: 0,
+            output: PhantomData,
+        })
+    }
+}
+
+impl<T> Iterator for Deserializer<T>
+    where T: Deserialize
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        let start = match self.document_starts.get(self.document_index) {
+            Some(&start) => start,
+            None => return None,
+        };
+        let end = self.document_starts
+            .get(self.document_index + 1)
+            .cloned()
+            .unwrap_or_else(|| self.events.len());
+        let aliases = &self.aliases[self.document_index];
+        self.document_index += 1;
+
+        let mut de = EventDeserializer {
+            events: &self.events,
+            aliases: aliases,
+            pos: start,
+        };
+        Some(Deserialize::deserialize(&mut de).and_then(|value| {
+            if de.pos == end {
+                Ok(value)
+            } else {
+                Err(Error::more_than_one_document())
+            }
+        }))
+    }
+}
+
 /// Decodes a YAML value from a `&str`.
+///
+/// This is a thin wrapper around `Deserializer::from_str` that errors if the
+/// input contains more than one `---`-separated document. To deserialize a
+/// stream of several documents, use `Deserializer::from_str` directly.
 pub fn from_str<T>(s: &str) -> Result<T>
     where T: Deserialize
 {
-    let mut parser = Parser::new(s.chars());
-    let mut loader = Loader {
-        events: Vec::new(),
-        aliases: BTreeMap::new(),
+    let mut documents = Deserializer::from_str(s)?;
+    let first = match documents.next() {
+        Some(result) => result?,
+        None => return Err(Error::end_of_stream()),
     };
-    parser.load(&mut loader, true)?;
-    if loader.events.is_empty() {
-        Err(Error::end_of_stream())
-    } else {
-        let mut deserializer = Deserializer {
-            events: &loader.events,
-            aliases: &loader.aliases,
-            pos: 0,
-        };
-        let t = Deserialize::deserialize(&mut deserializer)?;
-        if deserializer.pos == loader.events.len() {
-            Ok(t)
-        } else {
-            Err(Error::more_than_one_document())
-        }
+    if documents.next().is_some() {
+        return Err(Error::more_than_one_document());
     }
+    Ok(first)
 }
 
 pub fn from_iter<I, T>(iter: I) -> Result<T>
@@ -543,3 +850,38 @@ pub fn from_slice<T>(v: &[u8]) -> Result<T>
 {
     from_iter(v.iter().map(|byte| Ok(*byte)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::decode_base64;
+
+    #[test]
+    fn decodes_empty_input() {
+        assert_eq!(decode_base64(""), Some(Vec::new()));
+    }
+
+    #[test]
+    fn decodes_input_with_one_padding_character() {
+        assert_eq!(decode_base64("aGVsbG8="), Some(b"hello".to_vec()));
+    }
+
+    #[test]
+    fn decodes_input_with_two_padding_characters() {
+        assert_eq!(decode_base64("aA=="), Some(b"h".to_vec()));
+    }
+
+    #[test]
+    fn rejects_length_not_a_multiple_of_four() {
+        assert_eq!(decode_base64("aGVsbG8"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_characters() {
+        assert_eq!(decode_base64("aGVs!G8="), None);
+    }
+
+    #[test]
+    fn rejects_padding_before_the_end_of_a_group() {
+        assert_eq!(decode_base64("a=Vs"), None);
+    }
+}
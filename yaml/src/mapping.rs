@@ -0,0 +1,124 @@
+// Copyright 2016 Serde YAML Developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An insertion-order-preserving YAML mapping.
+
+use std::iter::FromIterator;
+use std::mem;
+use std::slice;
+
+use super::value::Value;
+
+/// A YAML mapping in which the entries retain the order they were inserted
+/// in, rather than being sorted by key. This matches the way a YAML mapping
+/// reads on the page, which callers generally expect to be preserved.
+#[derive(Clone, Debug, Default)]
+pub struct Mapping {
+    entries: Vec<(Value, Value)>,
+}
+
+// Two mappings are equal if they hold the same key/value pairs, regardless
+// of insertion order: a derived, positional `PartialEq` would make mappings
+// built from the same data in a different order compare unequal, which is
+// surprising for a map type.
+impl PartialEq for Mapping {
+    fn eq(&self, other: &Mapping) -> bool {
+        self.entries.len() == other.entries.len()
+            && self.entries.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl Eq for Mapping {}
+
+impl ::std::hash::Hash for Mapping {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        // Combine entry hashes order-independently so that equal mappings
+        // (per the `PartialEq` above) always hash equally.
+        let mut combined: u64 = 0;
+        for entry in &self.entries {
+            let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+            entry.hash(&mut hasher);
+            combined ^= hasher.finish();
+        }
+        combined.hash(state);
+    }
+}
+
+impl Mapping {
+    /// Creates an empty mapping.
+    pub fn new() -> Self {
+        Mapping { entries: Vec::new() }
+    }
+
+    /// Inserts a key-value pair, returning the previous value for that key
+    /// if one was present. The position of an existing key is unchanged; a
+    /// new key is appended at the end.
+    pub fn insert(&mut self, k: Value, v: Value) -> Option<Value> {
+        for entry in &mut self.entries {
+            if entry.0 == k {
+                return Some(mem::replace(&mut entry.1, v));
+            }
+        }
+        self.entries.push((k, v));
+        None
+    }
+
+    pub fn get(&self, k: &Value) -> Option<&Value> {
+        self.entries.iter().find(|entry| &entry.0 == k).map(|entry| &entry.1)
+    }
+
+    pub fn contains_key(&self, k: &Value) -> bool {
+        self.get(k).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter {
+        Iter { inner: self.entries.iter() }
+    }
+}
+
+impl FromIterator<(Value, Value)> for Mapping {
+    fn from_iter<I>(iter: I) -> Self
+        where I: IntoIterator<Item = (Value, Value)>
+    {
+        let mut mapping = Mapping::new();
+        for (k, v) in iter {
+            mapping.insert(k, v);
+        }
+        mapping
+    }
+}
+
+impl<'a> IntoIterator for &'a Mapping {
+    type Item = &'a (Value, Value);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a `Mapping`, in insertion order.
+pub struct Iter<'a> {
+    inner: slice::Iter<'a, (Value, Value)>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a (Value, Value);
+
+    fn next(&mut self) -> Option<&'a (Value, Value)> {
+        self.inner.next()
+    }
+}
@@ -0,0 +1,376 @@
+// Copyright 2016 Serde YAML Developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Serialization into the `Value` intermediate representation.
+
+use serde::ser::{self, Serialize};
+
+use super::error::{Error, Result};
+use super::mapping::Mapping;
+use super::value::Value;
+
+/// Converts a `Serialize` value into a `Value`, YAML's owned representation
+/// of any valid document, without going through YAML text.
+pub fn to_value<T>(value: T) -> Result<Value>
+    where T: Serialize
+{
+    value.serialize(Serializer)
+}
+
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        Ok(Value::I64(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        if v >= i64::min_value() as i128 && v <= i64::max_value() as i128 {
+            Ok(Value::I64(v as i64))
+        } else if v >= 0 && v <= u64::max_value() as i128 {
+            Ok(Value::U64(v as u64))
+        } else {
+            Ok(Value::I128(v))
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        if v <= i64::max_value() as u64 {
+            Ok(Value::I64(v as i64))
+        } else {
+            Ok(Value::U64(v))
+        }
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        if v <= i64::max_value() as u128 {
+            Ok(Value::I64(v as i64))
+        } else if v <= u64::max_value() as u128 {
+            Ok(Value::U64(v as u64))
+        } else if v <= i128::max_value() as u128 {
+            Ok(Value::I128(v as i128))
+        } else {
+            Ok(Value::U128(v))
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        Ok(Value::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        let vec = v.iter().map(|&b| Value::I64(b as i64)).collect();
+        Ok(Value::Sequence(vec))
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Value>
+        where T: Serialize
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::String(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value>
+        where T: Serialize
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value>
+        where T: Serialize
+    {
+        let mut map = Mapping::new();
+        map.insert(Value::String(variant.to_owned()), to_value(value)?);
+        Ok(Value::Mapping(map))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec> {
+        Ok(SerializeVec { vec: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeTupleVariant> {
+        Ok(SerializeTupleVariant {
+            variant: variant,
+            vec: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap> {
+        Ok(SerializeMap { map: Mapping::new(), next_key: None })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap> {
+        let _ = len;
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeStructVariant> {
+        Ok(SerializeStructVariant {
+            variant: variant,
+            map: Mapping::new(),
+        })
+    }
+}
+
+struct SerializeVec {
+    vec: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+        where T: Serialize
+    {
+        self.vec.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::Sequence(self.vec))
+    }
+}
+
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<()>
+        where T: Serialize
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+        where T: Serialize
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeTupleVariant {
+    variant: &'static str,
+    vec: Vec<Value>,
+}
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<()>
+        where T: Serialize
+    {
+        self.vec.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        let mut map = Mapping::new();
+        map.insert(Value::String(self.variant.to_owned()), Value::Sequence(self.vec));
+        Ok(Value::Mapping(map))
+    }
+}
+
+struct SerializeMap {
+    map: Mapping,
+    next_key: Option<Value>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<()>
+        where T: Serialize
+    {
+        self.next_key = Some(to_value(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
+        where T: Serialize
+    {
+        let key = self.next_key.take().expect("serialize_value called before serialize_key");
+        self.map.insert(key, to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::Mapping(self.map))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+        where T: Serialize
+    {
+        self.map.insert(Value::String(key.to_owned()), to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::Mapping(self.map))
+    }
+}
+
+struct SerializeStructVariant {
+    variant: &'static str,
+    map: Mapping,
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+        where T: Serialize
+    {
+        self.map.insert(Value::String(key.to_owned()), to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        let mut outer = Mapping::new();
+        outer.insert(Value::String(self.variant.to_owned()), Value::Mapping(self.map));
+        Ok(Value::Mapping(outer))
+    }
+}
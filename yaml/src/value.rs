@@ -0,0 +1,563 @@
+// Copyright 2016 Serde YAML Developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The `Value` enum, a generic representation of any valid YAML value.
+//!
+//! Parsing untyped YAML, merging or inspecting documents before committing
+//! to a concrete type, and round-tripping data through a typed model are all
+//! built on top of this intermediate representation.
+
+use std::slice;
+
+use serde::de::{self, Deserialize, DeserializeSeed, Unexpected};
+
+use super::error::{Error, Result};
+use super::mapping::{self, Mapping};
+
+/// Represents any valid YAML value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// Represents a YAML null value.
+    Null,
+    /// Represents a YAML boolean.
+    Bool(bool),
+    /// Represents a YAML integer.
+    I64(i64),
+    /// Represents a YAML integer too large to fit in `i64` but that fits in
+    /// `u64`, such as a 64-bit ID or hash above `i64::MAX`.
+    U64(u64),
+    /// Represents a YAML integer too large to fit in `u64` (either negative
+    /// beyond `i64::MIN`, or positive beyond `u64::MAX`) but that fits in
+    /// `i128`.
+    I128(i128),
+    /// Represents a YAML integer too large to fit in `i128`, i.e. a
+    /// non-negative value above `i128::MAX`.
+    U128(u128),
+    /// Represents a YAML floating point number.
+    F64(f64),
+    /// Represents a YAML string.
+    String(String),
+    /// Represents a YAML sequence in which the elements are `Value`.
+    Sequence(Vec<Value>),
+    /// Represents a YAML mapping in which the keys and values are `Value`.
+    Mapping(Mapping),
+}
+
+// `F64` makes this not strictly reflexive, but NaN keys/values in a document
+// round trip as whatever bit pattern the scanner produced, so treating the
+// derived PartialEq as total equality here is what callers expect.
+impl Eq for Value {}
+
+impl ::std::hash::Hash for Value {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        match *self {
+            Value::Null => 0u8.hash(state),
+            Value::Bool(b) => b.hash(state),
+            Value::I64(i) => i.hash(state),
+            Value::U64(u) => u.hash(state),
+            Value::I128(i) => i.hash(state),
+            Value::U128(u) => u.hash(state),
+            Value::F64(f) => f.to_bits().hash(state),
+            Value::String(ref s) => s.hash(state),
+            Value::Sequence(ref seq) => seq.hash(state),
+            Value::Mapping(ref map) => map.hash(state),
+        }
+    }
+}
+
+impl Deserialize for Value {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where D: de::Deserializer
+    {
+        struct ValueVisitor;
+
+        impl de::Visitor for ValueVisitor {
+            type Value = Value;
+
+            fn visit_bool<E>(self, b: bool) -> ::std::result::Result<Value, E> {
+                Ok(Value::Bool(b))
+            }
+
+            fn visit_i64<E>(self, i: i64) -> ::std::result::Result<Value, E> {
+                Ok(Value::I64(i))
+            }
+
+            fn visit_u64<E>(self, u: u64) -> ::std::result::Result<Value, E> {
+                if u <= i64::max_value() as u64 {
+                    Ok(Value::I64(u as i64))
+                } else {
+                    Ok(Value::U64(u))
+                }
+            }
+
+            fn visit_i128<E>(self, i: i128) -> ::std::result::Result<Value, E> {
+                if i >= i64::min_value() as i128 && i <= i64::max_value() as i128 {
+                    Ok(Value::I64(i as i64))
+                } else if i >= 0 && i <= u64::max_value() as i128 {
+                    Ok(Value::U64(i as u64))
+                } else {
+                    Ok(Value::I128(i))
+                }
+            }
+
+            fn visit_u128<E>(self, u: u128) -> ::std::result::Result<Value, E> {
+                if u <= i64::max_value() as u128 {
+                    Ok(Value::I64(u as i64))
+                } else if u <= u64::max_value() as u128 {
+                    Ok(Value::U64(u as u64))
+                } else if u <= i128::max_value() as u128 {
+                    Ok(Value::I128(u as i128))
+                } else {
+                    Ok(Value::U128(u))
+                }
+            }
+
+            fn visit_f64<E>(self, f: f64) -> ::std::result::Result<Value, E> {
+                Ok(Value::F64(f))
+            }
+
+            fn visit_str<E>(self, s: &str) -> ::std::result::Result<Value, E>
+                where E: de::Error
+            {
+                Ok(Value::String(s.to_owned()))
+            }
+
+            fn visit_string<E>(self, s: String) -> ::std::result::Result<Value, E> {
+                Ok(Value::String(s))
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> ::std::result::Result<Value, E>
+                where E: de::Error
+            {
+                let vec = v.iter().map(|&b| Value::I64(b as i64)).collect();
+                Ok(Value::Sequence(vec))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> ::std::result::Result<Value, E>
+                where E: de::Error
+            {
+                self.visit_bytes(&v)
+            }
+
+            fn visit_unit<E>(self) -> ::std::result::Result<Value, E> {
+                Ok(Value::Null)
+            }
+
+            fn visit_none<E>(self) -> ::std::result::Result<Value, E> {
+                Ok(Value::Null)
+            }
+
+            fn visit_some<D>(self, deserializer: D) -> ::std::result::Result<Value, D::Error>
+                where D: de::Deserializer
+            {
+                Deserialize::deserialize(deserializer)
+            }
+
+            fn visit_seq<V>(self, mut visitor: V) -> ::std::result::Result<Value, V::Error>
+                where V: de::SeqVisitor
+            {
+                let mut vec = Vec::new();
+                while let Some(elem) = de::SeqVisitor::visit(&mut visitor)? {
+                    vec.push(elem);
+                }
+                Ok(Value::Sequence(vec))
+            }
+
+            fn visit_map<V>(self, mut visitor: V) -> ::std::result::Result<Value, V::Error>
+                where V: de::MapVisitor
+            {
+                let mut map = Mapping::new();
+                while let Some((k, v)) = de::MapVisitor::visit(&mut visitor)? {
+                    map.insert(k, v);
+                }
+                Ok(Value::Mapping(map))
+            }
+        }
+
+        deserializer.deserialize(ValueVisitor)
+    }
+}
+
+/// Interprets a `Value` as an instance of type `T`.
+///
+/// This conversion can fail if the structure of the `Value` does not match
+/// the structure expected by `T`, for example if `T` is a struct type but
+/// the `Value` contains a sequence.
+pub fn from_value<T>(value: Value) -> Result<T>
+    where T: Deserialize
+{
+    Deserialize::deserialize(Deserializer { value: &value })
+}
+
+/// A deserializer that borrows from a `Value` rather than holding a cursor
+/// into a stream of parser events, so that a `Value` can be re-deserialized
+/// into a concrete type the same way a YAML string is.
+struct Deserializer<'de> {
+    value: &'de Value,
+}
+
+impl<'de> de::Deserializer for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        match *self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::I64(i) => visitor.visit_i64(i),
+            Value::U64(u) => visitor.visit_u64(u),
+            Value::I128(i) => visitor.visit_i128(i),
+            Value::U128(u) => visitor.visit_u128(u),
+            Value::F64(f) => visitor.visit_f64(f),
+            Value::String(ref s) => visitor.visit_str(s),
+            Value::Sequence(ref seq) => {
+                visitor.visit_seq(&mut SeqDeserializer { iter: seq.iter() })
+            }
+            Value::Mapping(ref map) => {
+                visitor.visit_map(&mut MapDeserializer { iter: map.iter(), value: None })
+            }
+        }
+    }
+
+    /// Parses `Null` as `None` and any other value as `Some(...)`.
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        match *self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    /// Parses a newtype struct as the underlying value.
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V
+    ) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Parses an enum as a single key:value pair where the key identifies the
+    /// variant, mirroring how `de::Deserializer::deserialize_enum` treats a
+    /// singleton mapping. A string also parses correctly as a unit variant.
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        let (variant, value) = match *self.value {
+            Value::Mapping(ref map) => {
+                let mut iter = map.iter();
+                let (variant, value) = match iter.next() {
+                    Some(&(Value::String(ref k), ref v)) => (k.as_str(), Some(v)),
+                    Some(&(ref k, _)) => {
+                        return Err(de::Error::invalid_type(unexpected(k), &"string"));
+                    }
+                    None => {
+                        return Err(de::Error::invalid_value(
+                            Unexpected::Map, &"map with a single key",
+                        ));
+                    }
+                };
+                if iter.next().is_some() {
+                    return Err(de::Error::invalid_value(
+                        Unexpected::Map, &"map with a single key",
+                    ));
+                }
+                (variant, value)
+            }
+            Value::String(ref s) => (s.as_str(), None),
+            ref other => {
+                return Err(de::Error::invalid_type(unexpected(other), &"string or map"));
+            }
+        };
+        visitor.visit_enum(EnumDeserializer { variant: variant, value: value })
+    }
+
+    forward_to_deserialize!{
+        bool u8 u16 u32 u64 i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        unit seq seq_fixed_size bytes byte_buf map unit_struct tuple_struct
+        struct struct_field tuple ignored_any
+    }
+}
+
+fn unexpected(value: &Value) -> Unexpected {
+    match *value {
+        Value::Null => Unexpected::Unit,
+        Value::Bool(b) => Unexpected::Bool(b),
+        Value::I64(i) => Unexpected::Signed(i),
+        // `Unexpected` has no 128-bit variants; these error messages fall
+        // back to the nearest 64-bit representation rather than losing the
+        // value itself, which is only ever stored in the `Value` variant.
+        Value::U64(u) => Unexpected::Unsigned(u),
+        Value::I128(i) => {
+            if i < 0 {
+                Unexpected::Signed(i64::min_value())
+            } else {
+                Unexpected::Unsigned(u64::max_value())
+            }
+        }
+        Value::U128(_) => Unexpected::Unsigned(u64::max_value()),
+        Value::F64(f) => Unexpected::Float(f),
+        Value::String(ref s) => Unexpected::Str(s),
+        Value::Sequence(_) => Unexpected::Seq,
+        Value::Mapping(_) => Unexpected::Map,
+    }
+}
+
+struct SeqDeserializer<'de> {
+    iter: slice::Iter<'de, Value>,
+}
+
+impl<'de> de::SeqVisitor for SeqDeserializer<'de> {
+    type Error = Error;
+
+    fn visit_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+        where T: DeserializeSeed
+    {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(Deserializer { value: value }).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct MapDeserializer<'de> {
+    iter: mapping::Iter<'de>,
+    value: Option<&'de Value>,
+}
+
+impl<'de> de::MapVisitor for MapDeserializer<'de> {
+    type Error = Error;
+
+    fn visit_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+        where K: DeserializeSeed
+    {
+        match self.iter.next() {
+            Some(&(ref k, ref v)) => {
+                self.value = Some(v);
+                seed.deserialize(Deserializer { value: k }).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn visit_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+        where V: DeserializeSeed
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(Deserializer { value: value }),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+}
+
+struct EnumDeserializer<'de> {
+    variant: &'de str,
+    value: Option<&'de Value>,
+}
+
+impl<'de> de::EnumVisitor for EnumDeserializer<'de> {
+    type Error = Error;
+    type Variant = VariantDeserializer<'de>;
+
+    fn visit_variant_seed<V>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant)>
+        where V: DeserializeSeed
+    {
+        let variant = seed.deserialize(StrDeserializer { value: self.variant })?;
+        Ok((variant, VariantDeserializer { value: self.value }))
+    }
+}
+
+struct VariantDeserializer<'de> {
+    value: Option<&'de Value>,
+}
+
+impl<'de> de::VariantVisitor for VariantDeserializer<'de> {
+    type Error = Error;
+
+    fn visit_unit(self) -> Result<()> {
+        match self.value {
+            Some(value) => Deserialize::deserialize(Deserializer { value: value }),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_newtype_seed<T>(self, seed: T) -> Result<T::Value>
+        where T: DeserializeSeed
+    {
+        match self.value {
+            Some(value) => seed.deserialize(Deserializer { value: value }),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"newtype variant")),
+        }
+    }
+
+    fn visit_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        match self.value {
+            Some(value) => de::Deserializer::deserialize(Deserializer { value: value }, visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"tuple variant")),
+        }
+    }
+
+    fn visit_struct<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V
+    ) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        match self.value {
+            Some(value) => de::Deserializer::deserialize(Deserializer { value: value }, visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"struct variant")),
+        }
+    }
+}
+
+/// Feeds a single borrowed string into a seed, used to deserialize an enum's
+/// variant name without allocating.
+struct StrDeserializer<'de> {
+    value: &'de str,
+}
+
+impl<'de> de::Deserializer for StrDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize<V>(self, visitor: V) -> Result<V::Value>
+        where V: de::Visitor
+    {
+        visitor.visit_str(self.value)
+    }
+
+    forward_to_deserialize!{
+        bool u8 u16 u32 u64 i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        unit option seq seq_fixed_size bytes byte_buf map unit_struct
+        tuple_struct struct struct_field tuple ignored_any
+    }
+}
+
+/// The sentinel struct name `TaggedValue::deserialize` asks the deserializer
+/// to recognize; `de::Deserializer::deserialize_struct` handles this name
+/// specially rather than treating it as an ordinary YAML mapping.
+pub(crate) const TAGGED_VALUE_NAME: &'static str = "$serde_yaml::private::TaggedValue";
+pub(crate) const TAGGED_VALUE_FIELDS: &'static [&'static str] = &["tag", "value"];
+
+/// A YAML value together with the non-core-schema tag attached to it, such
+/// as the `!Point` in `!Point "1,2"`. Values with no tag, or only a `!!`
+/// core-schema tag, deserialize into a `TaggedValue` with an empty `tag`
+/// rather than failing.
+///
+/// Only scalars carry a tag that `TaggedValue` can see: the underlying
+/// `peek_tag` only inspects `Event::Scalar`, so a tag on a mapping or
+/// sequence (e.g. `!Point {x: 1, y: 2}`) is reported as empty rather than
+/// `"!Point"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaggedValue {
+    /// The tag attached to the value, including its handle, e.g.
+    /// `"!Point"`. Empty when the input carried no application-specific tag.
+    pub tag: String,
+    /// The value with the tag stripped off, interpreted the same way an
+    /// untagged value would be.
+    pub value: Value,
+}
+
+impl Deserialize for TaggedValue {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+        where D: de::Deserializer
+    {
+        struct TaggedValueVisitor;
+
+        impl de::Visitor for TaggedValueVisitor {
+            type Value = TaggedValue;
+
+            fn visit_map<V>(self, mut visitor: V) -> ::std::result::Result<TaggedValue, V::Error>
+                where V: de::MapVisitor
+            {
+                let mut tag = String::new();
+                let mut value = Value::Null;
+                while let Some((key, entry)) = de::MapVisitor::visit::<String, Value>(&mut visitor)? {
+                    match key.as_ref() {
+                        "tag" => {
+                            if let Value::String(s) = entry {
+                                tag = s;
+                            }
+                        }
+                        "value" => value = entry,
+                        _ => {}
+                    }
+                }
+                Ok(TaggedValue { tag: tag, value: value })
+            }
+        }
+
+        deserializer.deserialize_struct(TAGGED_VALUE_NAME, TAGGED_VALUE_FIELDS, TaggedValueVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_value, Value};
+    use super::super::ser::to_value;
+
+    #[test]
+    fn round_trips_i64() {
+        let value = to_value(-5i64).unwrap();
+        assert_eq!(value, Value::I64(-5));
+        assert_eq!(from_value::<i64>(value).unwrap(), -5);
+    }
+
+    #[test]
+    fn widens_u64_beyond_i64_max_instead_of_losing_precision() {
+        let huge = u64::max_value();
+        let value = to_value(huge).unwrap();
+        assert_eq!(value, Value::U64(huge));
+        assert_eq!(from_value::<u64>(value).unwrap(), huge);
+    }
+
+    #[test]
+    fn widens_u128_beyond_u64_max_instead_of_losing_precision() {
+        let huge = u128::max_value();
+        let value = to_value(huge).unwrap();
+        assert_eq!(value, Value::U128(huge));
+        assert_eq!(from_value::<u128>(value).unwrap(), huge);
+    }
+
+    #[test]
+    fn widens_negative_i128_beyond_i64_min_instead_of_losing_precision() {
+        let huge = i128::min_value();
+        let value = to_value(huge).unwrap();
+        assert_eq!(value, Value::I128(huge));
+        assert_eq!(from_value::<i128>(value).unwrap(), huge);
+    }
+
+    #[test]
+    fn mappings_compare_equal_regardless_of_insertion_order() {
+        use super::super::mapping::Mapping;
+
+        let mut forward = Mapping::new();
+        forward.insert(Value::String("a".to_owned()), Value::I64(1));
+        forward.insert(Value::String("b".to_owned()), Value::I64(2));
+
+        let mut backward = Mapping::new();
+        backward.insert(Value::String("b".to_owned()), Value::I64(2));
+        backward.insert(Value::String("a".to_owned()), Value::I64(1));
+
+        assert_eq!(Value::Mapping(forward), Value::Mapping(backward));
+    }
+}